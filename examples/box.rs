@@ -7,8 +7,7 @@ extern crate rand;
 extern crate "crypto-nacl" as nacl;
 
 use rand::{ Rng, OsRng };
-use nacl::{ crypto_box_SECRETKEYBYTES, crypto_box_NONCEBYTES,
-  crypto_box_keypair, crypto_box, crypto_box_open };
+use nacl::{ crypto_box_NONCEBYTES, SecretKey, crypto_box, crypto_box_open };
 
 fn main() {
     //
@@ -22,11 +21,10 @@ fn main() {
     let test_msg = "Example plaintext data.".as_bytes();
 
     // Generate a keypair using a cryptographically strong entropy source.
-    let mut alice_sk = [0u8; crypto_box_SECRETKEYBYTES];
-    let alice_pk = crypto_box_keypair(&mut alice_sk);
-
-    let mut bob_sk = [0u8; crypto_box_SECRETKEYBYTES];
-    let bob_pk = crypto_box_keypair(&mut bob_sk);
+    // The secret keys are wrapped in SecretKey, which zeroes its contents
+    // when dropped.
+    let (alice_sk, alice_pk) = SecretKey::generate();
+    let (bob_sk, bob_pk) = SecretKey::generate();
 
     // (NOT SHOWN) Alice/Bob exchange alice_pk/bob_pk. How this is done
     // is left up to the application. Note that if the exchange takes
@@ -7,6 +7,7 @@
 #![crate_name = "crypto-nacl"]
 
 #![feature(simd)]
+#![feature(core_intrinsics)]
 #![cfg_attr(test, feature(test))]
 
 extern crate rand;
@@ -17,5 +18,7 @@ extern crate crypto;
 // compatible routines.
 pub use pkbox::*;
 pub use secretbox::*;
+pub use secretstream::*;
 mod pkbox;
 mod secretbox;
+mod secretstream;
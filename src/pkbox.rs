@@ -4,13 +4,18 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use std::intrinsics::volatile_set_memory;
+
 use rand::{Rng, OsRng};
+use crypto::blake2b::Blake2b;
 use crypto::curve25519::{curve25519, curve25519_base};
 use crypto::digest::Digest;
 use crypto::salsa20::hsalsa20;
 use crypto::sha2::Sha512Trunc256;
 
-use secretbox::{crypto_secretbox, crypto_secretbox_open};
+use secretbox::{crypto_secretbox, crypto_secretbox_open,
+  crypto_secretbox_detached, crypto_secretbox_open_detached,
+  crypto_secretbox_OVERHEAD};
 
 /// The length of the crypto_box public key in bytes.
 #[allow(non_upper_case_globals)]
@@ -30,6 +35,44 @@ pub const crypto_box_OVERHEAD: usize = 16;
 
 static ZERO_HSALSA_NONCE: [u8; 16] = [0u8; 16];
 
+/// A crypto_box secret (private) key.
+///
+/// SecretKey owns its crypto_box_SECRETKEYBYTES of key material, cannot be
+/// copied or cloned, and overwrites its contents with zeroes when dropped.
+/// This gives the assurance that, even in the presence of a
+/// memory-safety bug elsewhere, a generated or decrypted private key is
+/// scrubbed rather than left lingering in a reusable stack or heap
+/// allocation.
+pub struct SecretKey([u8; crypto_box_SECRETKEYBYTES]);
+
+impl SecretKey {
+    /// Public-key authenticated encryption/decryption keypair generation.
+    ///
+    /// The generate function randomly generates a secret key and
+    /// corresponding public key, returning both.
+    pub fn generate() -> (SecretKey, [u8; crypto_box_PUBLICKEYBYTES]) {
+        let mut sk = [0u8; crypto_box_SECRETKEYBYTES];
+        let pk = crypto_box_keypair(&mut sk);
+        let key = SecretKey(sk);
+        unsafe {
+            volatile_set_memory(sk.as_mut_ptr(), 0, sk.len());
+        }
+        (key, pk)
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        unsafe {
+            volatile_set_memory(self.0.as_mut_ptr(), 0, self.0.len());
+        }
+    }
+}
+
 /// Public-key authenticated encryption/decryption keypair generation.
 ///
 /// The crypto_box_keypair function randomly generates a secret key and
@@ -37,6 +80,10 @@ static ZERO_HSALSA_NONCE: [u8; 16] = [0u8; 16];
 /// public key. This function asserts if sk.len() is not
 /// crypto_box_SECRETKEYBYTES and guarantees that pk has
 /// crypto_box_PUBLICKEYBYTES.
+///
+/// This is the low-level primitive behind [`SecretKey::generate`]; prefer
+/// SecretKey::generate() unless the raw bytes genuinely need to outlive a
+/// SecretKey wrapper, since sk will not be zeroed on the caller's behalf.
 pub fn crypto_box_keypair(sk: &mut [u8]) -> [u8; crypto_box_PUBLICKEYBYTES] {
     assert!(sk.len() == crypto_box_PUBLICKEYBYTES);
 
@@ -66,12 +113,11 @@ pub fn crypto_box_keypair(sk: &mut [u8]) -> [u8; crypto_box_PUBLICKEYBYTES] {
 /// # Arguments
 /// * pk - The public key.
 /// * sk - The secret (private) key.
-pub fn crypto_box_beforenm(pk: &[u8], sk: &[u8]) -> [u8; crypto_box_SECRETKEYBYTES] {
+pub fn crypto_box_beforenm(pk: &[u8], sk: &SecretKey) -> [u8; crypto_box_SECRETKEYBYTES] {
     assert!(pk.len() == crypto_box_PUBLICKEYBYTES);
-    assert!(sk.len() == crypto_box_SECRETKEYBYTES);
 
     // Obtain the shared secret with a Curve25519 scalar mult.
-    let curve_key = curve25519(sk, pk);
+    let curve_key = curve25519(sk.as_slice(), pk);
 
     // Derive the crypto_secretbox key with HSalsa20.
     let mut key = [0u8; 32];
@@ -85,8 +131,8 @@ pub fn crypto_box_beforenm(pk: &[u8], sk: &[u8]) -> [u8; crypto_box_SECRETKEYBYT
 /// The crypto_box function encrypts and authenticates a message, using the
 /// sender's secret key, the receiver's public key, and a nonce, returning
 /// the corresponding ciphertext. This function asserts if pk.len() is not
-/// crypto_box_PUBLICKEYBYTES, sk.len() is not crypto_box_SECRETKEYBYTES,
-/// or if nonce.len() is not crypto_secretbox_NONCEBYTES.
+/// crypto_box_PUBLICKEYBYTES, or if nonce.len() is not
+/// crypto_secretbox_NONCEBYTES.
 ///
 /// Nonces MUST NOT be reused with a given pk/sk pair. Nonces are long enough
 /// that randomly generated nonces have negligible risk of collision.
@@ -96,7 +142,7 @@ pub fn crypto_box_beforenm(pk: &[u8], sk: &[u8]) -> [u8; crypto_box_SECRETKEYBYT
 /// * nonce - The nonce to use for the encryption/authentication.
 /// * pk - The receiver's public key.
 /// * sk - The sender's secret (private) key.
-pub fn crypto_box(msg: &[u8], nonce: &[u8], pk: &[u8], sk: &[u8]) -> Vec<u8> {
+pub fn crypto_box(msg: &[u8], nonce: &[u8], pk: &[u8], sk: &SecretKey) -> Vec<u8> {
     assert!(nonce.len() == crypto_box_NONCEBYTES);
 
     let key = crypto_box_beforenm(pk, sk);
@@ -108,42 +154,153 @@ pub fn crypto_box(msg: &[u8], nonce: &[u8], pk: &[u8], sk: &[u8]) -> Vec<u8> {
 /// The crypto_box_open function authenticates and decrypts a ciphertext,
 /// using the sender's public key, the receiver's secret key, and a nonce,
 /// the corresponding plaintext. This function asserts if pk.len() is not
-/// crypto_box_PUBLICKEYBYTES, sk.len() is not crypto_box_SECRETKEYBYTES,
-/// or if nonce.len() is not crypto_secretbox_NONCEBYTES.
+/// crypto_box_PUBLICKEYBYTES, or if nonce.len() is not
+/// crypto_secretbox_NONCEBYTES.
 ///
 /// # Arguments
 /// * ciphertext - The ciphertext to authenticate/decrypt.
 /// * nonce - The nonce to use for the authentication/decryption.
 /// * pk - The sender's public key.
 /// * sk - The receiver's secret (private) key.
-pub fn crypto_box_open(ciphertext: &[u8], nonce: &[u8], pk: &[u8], sk: &[u8]) -> Result<Vec<u8>, &'static str> {
+pub fn crypto_box_open(ciphertext: &[u8], nonce: &[u8], pk: &[u8], sk: &SecretKey) -> Result<Vec<u8>, &'static str> {
     assert!(nonce.len() == crypto_box_NONCEBYTES);
 
     let key = crypto_box_beforenm(pk, sk);
     crypto_secretbox_open(ciphertext, nonce, &key)
 }
 
+/// Public-key authenticated encryption, with the Poly1305 authenticator
+/// returned separately from the ciphertext.
+///
+/// This is the crypto_box counterpart of crypto_secretbox_detached: the
+/// returned ciphertext is always exactly msg.len() bytes, with the
+/// crypto_box_OVERHEAD-byte authenticator returned alongside it instead of
+/// prepended.
+///
+/// # Arguments
+/// * msg - The plaintext to encrypt/authenticate.
+/// * nonce - The nonce to use for the encryption/authentication.
+/// * pk - The receiver's public key.
+/// * sk - The sender's secret (private) key.
+pub fn crypto_box_detached(msg: &[u8], nonce: &[u8], pk: &[u8], sk: &SecretKey) -> (Vec<u8>, [u8; crypto_box_OVERHEAD]) {
+    assert!(nonce.len() == crypto_box_NONCEBYTES);
+
+    let key = crypto_box_beforenm(pk, sk);
+    crypto_secretbox_detached(msg, nonce, &key)
+}
+
+/// Public-key authenticated decryption of a detached ciphertext/tag pair
+/// produced by crypto_box_detached.
+///
+/// # Arguments
+/// * ciphertext - The ciphertext to authenticate/decrypt.
+/// * tag - The Poly1305 authenticator returned alongside the ciphertext.
+/// * nonce - The nonce to use for the authentication/decryption.
+/// * pk - The sender's public key.
+/// * sk - The receiver's secret (private) key.
+pub fn crypto_box_open_detached(ciphertext: &[u8], tag: &[u8], nonce: &[u8], pk: &[u8], sk: &SecretKey) -> Result<Vec<u8>, &'static str> {
+    assert!(nonce.len() == crypto_box_NONCEBYTES);
+    assert!(tag.len() == crypto_secretbox_OVERHEAD);
+
+    let key = crypto_box_beforenm(pk, sk);
+    crypto_secretbox_open_detached(ciphertext, tag, nonce, &key)
+}
+
+/// The length of the crypto_box_seal overhead in bytes.
+#[allow(non_upper_case_globals)]
+pub const crypto_box_SEALBYTES: usize = crypto_box_PUBLICKEYBYTES + crypto_box_OVERHEAD;
+
+/// Anonymous, repudiable, public-key authenticated encryption.
+///
+/// The crypto_box_seal function encrypts a message to a recipient's public
+/// key alone: it generates a throwaway ephemeral keypair, derives a nonce
+/// from the ephemeral and recipient public keys, and crypto_boxes the
+/// message under that ephemeral keypair. The returned ciphertext is the
+/// ephemeral public key followed by the crypto_box output, and is
+/// crypto_box_SEALBYTES longer than msg.
+///
+/// Unlike crypto_box, there is no sender key to manage or reveal, so
+/// crypto_box_seal is appropriate when the sender's identity does not need
+/// to be (or must not be) authenticated, such as submitting data to a
+/// known recipient anonymously.
+///
+/// # Arguments
+/// * msg - The plaintext to encrypt.
+/// * recipient_pk - The recipient's public key.
+pub fn crypto_box_seal(msg: &[u8], recipient_pk: &[u8]) -> Vec<u8> {
+    assert!(recipient_pk.len() == crypto_box_PUBLICKEYBYTES);
+
+    let (ephemeral_sk, ephemeral_pk) = SecretKey::generate();
+
+    let nonce = crypto_box_seal_nonce(&ephemeral_pk, recipient_pk);
+    let boxed = crypto_box(msg, &nonce, recipient_pk, &ephemeral_sk);
+
+    let mut out = Vec::with_capacity(ephemeral_pk.len() + boxed.len());
+    out.extend(ephemeral_pk.iter().cloned());
+    out.extend(boxed);
+    out
+}
+
+/// Anonymous, repudiable, public-key authenticated decryption.
+///
+/// The crypto_box_seal_open function authenticates and decrypts a
+/// ciphertext produced by crypto_box_seal, using the recipient's keypair.
+/// This function asserts if recipient_pk.len() is not
+/// crypto_box_PUBLICKEYBYTES. It returns an error if ciphertext is too
+/// short to contain an ephemeral public key.
+///
+/// # Arguments
+/// * ciphertext - The ciphertext to authenticate/decrypt.
+/// * recipient_pk - The recipient's public key.
+/// * recipient_sk - The recipient's secret (private) key.
+pub fn crypto_box_seal_open(ciphertext: &[u8], recipient_pk: &[u8], recipient_sk: &SecretKey) -> Result<Vec<u8>, &'static str> {
+    assert!(recipient_pk.len() == crypto_box_PUBLICKEYBYTES);
+
+    if ciphertext.len() < crypto_box_PUBLICKEYBYTES {
+        return Err("crypto_box_seal_open: ciphertext too short to contain an ephemeral public key");
+    }
+
+    let (ephemeral_pk, boxed) = ciphertext.split_at(crypto_box_PUBLICKEYBYTES);
+    let nonce = crypto_box_seal_nonce(ephemeral_pk, recipient_pk);
+    crypto_box_open(boxed, &nonce, ephemeral_pk, recipient_sk)
+}
+
+/// Derives the crypto_box_seal nonce as a BLAKE2b hash of the ephemeral
+/// and recipient public keys, so that crypto_box_seal_open can recompute
+/// the same nonce without it needing to be transmitted separately.
+fn crypto_box_seal_nonce(ephemeral_pk: &[u8], recipient_pk: &[u8]) -> [u8; crypto_box_NONCEBYTES] {
+    let mut hasher = Blake2b::new(crypto_box_NONCEBYTES);
+    hasher.input(ephemeral_pk);
+    hasher.input(recipient_pk);
+
+    let mut nonce = [0u8; crypto_box_NONCEBYTES];
+    hasher.result(&mut nonce);
+    nonce
+}
+
 #[cfg(test)]
 mod test {
-    use pkbox::{crypto_box, crypto_box_open};
+    use pkbox::{crypto_box, crypto_box_open,
+      crypto_box_detached, crypto_box_open_detached,
+      crypto_box_seal, crypto_box_seal_open, SecretKey};
 
     #[test]
     fn test_nacl_box_vectors() {
-        let alicesk = vec![
+        let alicesk = SecretKey([
             0x77,0x07,0x6d,0x0a,0x73,0x18,0xa5,0x7d,
             0x3c,0x16,0xc1,0x72,0x51,0xb2,0x66,0x45,
             0xdf,0x4c,0x2f,0x87,0xeb,0xc0,0x99,0x2a,
-            0xb1,0x77,0xfb,0xa5,0x1d,0xb9,0x2c,0x2a ];
+            0xb1,0x77,0xfb,0xa5,0x1d,0xb9,0x2c,0x2a ]);
         let alicepk = vec![
             0x85,0x20,0xf0,0x09,0x89,0x30,0xa7,0x54,
             0x74,0x8b,0x7d,0xdc,0xb4,0x3e,0xf7,0x5a,
             0x0d,0xbf,0x3a,0x0d,0x26,0x38,0x1a,0xf4,
             0xeb,0xa4,0xa9,0x8e,0xaa,0x9b,0x4e,0x6a ];
-        let bobsk = vec![
+        let bobsk = SecretKey([
             0x5d,0xab,0x08,0x7e,0x62,0x4a,0x8a,0x4b,
             0x79,0xe1,0x7f,0x8b,0x83,0x80,0x0e,0xe6,
             0x6f,0x3b,0xb1,0x29,0x26,0x18,0xb6,0xfd,
-            0x1c,0x2f,0x8b,0x27,0xff,0x88,0xe0,0xeb ];
+            0x1c,0x2f,0x8b,0x27,0xff,0x88,0xe0,0xeb ]);
         let bobpk = vec![
             0xde,0x9e,0xdb,0x7d,0x7b,0x7d,0xc1,0xb4,
             0xd3,0x5b,0x61,0xc2,0xec,0xe4,0x35,0x37,
@@ -193,12 +350,40 @@ mod test {
             0xe3,0x55,0xa5
         ];
 
-        let boxed = crypto_box(&msg[], &nonce[], &bobpk[], &alicesk[]);
+        let boxed = crypto_box(&msg[], &nonce[], &bobpk[], &alicesk);
         assert!(boxed == box_expected);
 
-        match crypto_box_open(&box_expected[], &nonce[], &alicepk[], &bobsk[]) {
+        match crypto_box_open(&box_expected[], &nonce[], &alicepk[], &bobsk) {
             Ok(unboxed) => assert!(unboxed == msg),
             Err(_) => panic!()
         }
     }
+
+    #[test]
+    fn test_nacl_box_detached_roundtrip() {
+        let (alice_sk, alice_pk) = SecretKey::generate();
+        let (bob_sk, bob_pk) = SecretKey::generate();
+
+        let nonce = [0u8; 24];
+        let msg = "detached framing test".as_bytes();
+
+        let (ciphertext, tag) = crypto_box_detached(msg, &nonce, &bob_pk[], &alice_sk);
+        match crypto_box_open_detached(&ciphertext[], &tag, &nonce, &alice_pk[], &bob_sk) {
+            Ok(opened) => assert!(&opened[] == msg),
+            Err(_) => panic!()
+        }
+    }
+
+    #[test]
+    fn test_nacl_box_seal_roundtrip() {
+        let (recipient_sk, recipient_pk) = SecretKey::generate();
+
+        let msg = "an anonymous message".as_bytes();
+        let sealed = crypto_box_seal(msg, &recipient_pk[]);
+
+        match crypto_box_seal_open(&sealed[], &recipient_pk[], &recipient_sk) {
+            Ok(opened) => assert!(&opened[] == msg),
+            Err(_) => panic!()
+        }
+    }
 }
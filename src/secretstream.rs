@@ -0,0 +1,318 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::intrinsics::volatile_set_memory;
+
+use rand::{Rng, OsRng};
+use crypto::blake2b::Blake2b;
+use crypto::digest::Digest;
+
+use secretbox::{crypto_secretbox_KEYBYTES, crypto_secretbox_NONCEBYTES,
+  crypto_secretbox_OVERHEAD, crypto_secretbox_detached,
+  crypto_secretbox_open_detached, crypto_secretbox_xchacha20poly1305_detached,
+  crypto_secretbox_xchacha20poly1305_open_detached};
+
+/// The length of a crypto_secretstream key in bytes.
+#[allow(non_upper_case_globals)]
+pub const crypto_secretstream_KEYBYTES: usize = crypto_secretbox_KEYBYTES;
+
+/// The length of a crypto_secretstream stream's initial nonce prefix in
+/// bytes. Each chunk's actual nonce is this prefix with an 8-byte
+/// little-endian chunk counter appended.
+#[allow(non_upper_case_globals)]
+pub const crypto_secretstream_HEADERBYTES: usize = crypto_secretbox_NONCEBYTES - 8;
+
+/// The per-chunk overhead in bytes: one tag byte, plus the
+/// crypto_secretbox Poly1305 authenticator.
+#[allow(non_upper_case_globals)]
+pub const crypto_secretstream_OVERHEAD: usize = 1 + crypto_secretbox_OVERHEAD;
+
+/// A regular chunk; more chunks are expected to follow.
+pub const crypto_secretstream_TAG_MESSAGE: u8 = 0x00;
+
+/// The last chunk of a stream. A decryptor must refuse any chunk pushed
+/// after this one, so truncating a stream before this tag is reached is
+/// detectable.
+pub const crypto_secretstream_TAG_FINAL: u8 = 0x01;
+
+/// A chunk after which both sides derive a fresh sub-key from the
+/// current key and nonce prefix, and restart the chunk counter. Useful
+/// for bounding the amount of data authenticated under a single key in a
+/// very long-lived stream.
+pub const crypto_secretstream_TAG_REKEY: u8 = 0x02;
+
+/// The stream cipher/authenticator construction a secretstream chunk is
+/// protected with, selected once when the stream is opened.
+#[derive(Copy, Clone)]
+pub enum SecretStreamCipher {
+    /// crypto_secretbox's default XSalsa20-Poly1305 construction.
+    XSalsa20,
+    /// crypto_secretbox_xchacha20poly1305's XChaCha20-Poly1305 construction.
+    XChaCha20,
+}
+
+fn chunk_detached(cipher: SecretStreamCipher, framed: &[u8], nonce: &[u8], key: &[u8]) -> (Vec<u8>, [u8; crypto_secretbox_OVERHEAD]) {
+    match cipher {
+        SecretStreamCipher::XSalsa20 => crypto_secretbox_detached(framed, nonce, key),
+        SecretStreamCipher::XChaCha20 => crypto_secretbox_xchacha20poly1305_detached(framed, nonce, key),
+    }
+}
+
+fn chunk_open_detached(cipher: SecretStreamCipher, ct: &[u8], auth_tag: &[u8], nonce: &[u8], key: &[u8]) -> Result<Vec<u8>, &'static str> {
+    match cipher {
+        SecretStreamCipher::XSalsa20 => crypto_secretbox_open_detached(ct, auth_tag, nonce, key),
+        SecretStreamCipher::XChaCha20 => crypto_secretbox_xchacha20poly1305_open_detached(ct, auth_tag, nonce, key),
+    }
+}
+
+/// Derives the per-chunk nonce from a stream's fixed nonce prefix and its
+/// current chunk counter.
+fn chunk_nonce(nonce_prefix: &[u8], counter: u64) -> [u8; crypto_secretbox_NONCEBYTES] {
+    let mut nonce = [0u8; crypto_secretbox_NONCEBYTES];
+    nonce[0..crypto_secretstream_HEADERBYTES].copy_from_slice(nonce_prefix);
+    for i in 0..8 {
+        nonce[crypto_secretstream_HEADERBYTES + i] = (counter >> (8 * i)) as u8;
+    }
+    nonce
+}
+
+/// Derives the sub-key used after a TAG_REKEY chunk, as a BLAKE2b hash of
+/// the current key and nonce prefix.
+fn rekey(key: &[u8; crypto_secretstream_KEYBYTES], nonce_prefix: &[u8]) -> [u8; crypto_secretstream_KEYBYTES] {
+    let mut hasher = Blake2b::new(crypto_secretstream_KEYBYTES);
+    hasher.input(key);
+    hasher.input(nonce_prefix);
+
+    let mut out = [0u8; crypto_secretstream_KEYBYTES];
+    hasher.result(&mut out);
+    out
+}
+
+/// A stateful encryptor that splits a large message into independently
+/// authenticated chunks, so that the whole plaintext never needs to be
+/// buffered in memory at once.
+///
+/// Each chunk is encrypted with crypto_secretbox (XSalsa20/XChaCha20,
+/// depending on how the stream was opened) under the stream's key and a
+/// nonce derived from the stream's nonce prefix and a running chunk
+/// counter, and is tagged MESSAGE, FINAL, or REKEY so that a
+/// SecretStreamDecryptor can detect truncation. Like SecretKey, the
+/// stream's key is zeroed when the encryptor is dropped.
+pub struct SecretStreamEncryptor {
+    key: [u8; crypto_secretstream_KEYBYTES],
+    nonce_prefix: [u8; crypto_secretstream_HEADERBYTES],
+    counter: u64,
+    finalized: bool,
+    cipher: SecretStreamCipher,
+}
+
+impl Drop for SecretStreamEncryptor {
+    fn drop(&mut self) {
+        unsafe {
+            volatile_set_memory(self.key.as_mut_ptr(), 0, self.key.len());
+        }
+    }
+}
+
+impl SecretStreamEncryptor {
+    /// Begins a new encryption stream under `key`, using the default
+    /// XSalsa20-Poly1305 construction. Returns the encryptor and the
+    /// random header that the matching SecretStreamDecryptor needs to
+    /// reconstruct the per-chunk nonces.
+    pub fn new(key: &[u8]) -> (SecretStreamEncryptor, [u8; crypto_secretstream_HEADERBYTES]) {
+        SecretStreamEncryptor::new_with_cipher(key, SecretStreamCipher::XSalsa20)
+    }
+
+    /// Begins a new encryption stream under `key`, authenticating each
+    /// chunk with `cipher` instead of the default XSalsa20-Poly1305
+    /// construction. Returns the encryptor and the random header that the
+    /// matching SecretStreamDecryptor needs to reconstruct the per-chunk
+    /// nonces; the decryptor must be told which cipher was used
+    /// separately, since it is not encoded in the header.
+    pub fn new_with_cipher(key: &[u8], cipher: SecretStreamCipher) -> (SecretStreamEncryptor, [u8; crypto_secretstream_HEADERBYTES]) {
+        assert!(key.len() == crypto_secretstream_KEYBYTES);
+
+        let mut rng = OsRng::new().ok().unwrap();
+        let mut nonce_prefix = [0u8; crypto_secretstream_HEADERBYTES];
+        rng.fill_bytes(&mut nonce_prefix);
+
+        let mut k = [0u8; crypto_secretstream_KEYBYTES];
+        k.copy_from_slice(key);
+
+        let encryptor = SecretStreamEncryptor {
+            key: k,
+            nonce_prefix: nonce_prefix,
+            counter: 0,
+            finalized: false,
+            cipher: cipher,
+        };
+        (encryptor, nonce_prefix)
+    }
+
+    /// Encrypts and authenticates the next chunk of the stream, binding
+    /// `tag` into it so a decryptor can detect truncation or reordering.
+    ///
+    /// Panics if called again after a TAG_FINAL chunk has already been
+    /// pushed, since a finalized stream must not be extended.
+    pub fn push(&mut self, chunk: &[u8], tag: u8) -> Vec<u8> {
+        assert!(!self.finalized, "secretstream: stream already finalized");
+
+        let nonce = chunk_nonce(&self.nonce_prefix, self.counter);
+
+        let mut framed = Vec::with_capacity(1 + chunk.len());
+        framed.push(tag);
+        framed.extend(chunk.iter().cloned());
+        let (ciphertext, auth_tag) = chunk_detached(self.cipher, &framed, &nonce, &self.key);
+
+        self.counter += 1;
+        match tag {
+            crypto_secretstream_TAG_FINAL => self.finalized = true,
+            crypto_secretstream_TAG_REKEY => {
+                self.key = rekey(&self.key, &self.nonce_prefix);
+                self.counter = 0;
+            },
+            _ => {},
+        }
+
+        let mut out = Vec::with_capacity(auth_tag.len() + ciphertext.len());
+        out.extend(auth_tag.iter().cloned());
+        out.extend(ciphertext);
+        out
+    }
+}
+
+/// The decrypting counterpart of [`SecretStreamEncryptor`].
+pub struct SecretStreamDecryptor {
+    key: [u8; crypto_secretstream_KEYBYTES],
+    nonce_prefix: [u8; crypto_secretstream_HEADERBYTES],
+    counter: u64,
+    finalized: bool,
+    cipher: SecretStreamCipher,
+}
+
+impl Drop for SecretStreamDecryptor {
+    fn drop(&mut self) {
+        unsafe {
+            volatile_set_memory(self.key.as_mut_ptr(), 0, self.key.len());
+        }
+    }
+}
+
+impl SecretStreamDecryptor {
+    /// Begins decrypting a stream under `key`, using the header returned
+    /// by the encryptor's [`SecretStreamEncryptor::new`]. Assumes the
+    /// default XSalsa20-Poly1305 construction.
+    pub fn new(key: &[u8], header: &[u8]) -> SecretStreamDecryptor {
+        SecretStreamDecryptor::new_with_cipher(key, header, SecretStreamCipher::XSalsa20)
+    }
+
+    /// Begins decrypting a stream under `key`, using the header returned
+    /// by the encryptor's [`SecretStreamEncryptor::new_with_cipher`] and
+    /// the same `cipher` it was opened with.
+    pub fn new_with_cipher(key: &[u8], header: &[u8], cipher: SecretStreamCipher) -> SecretStreamDecryptor {
+        assert!(key.len() == crypto_secretstream_KEYBYTES);
+        assert!(header.len() == crypto_secretstream_HEADERBYTES);
+
+        let mut k = [0u8; crypto_secretstream_KEYBYTES];
+        k.copy_from_slice(key);
+        let mut nonce_prefix = [0u8; crypto_secretstream_HEADERBYTES];
+        nonce_prefix.copy_from_slice(header);
+
+        SecretStreamDecryptor {
+            key: k,
+            nonce_prefix: nonce_prefix,
+            counter: 0,
+            finalized: false,
+            cipher: cipher,
+        }
+    }
+
+    /// Authenticates and decrypts the next chunk of the stream, returning
+    /// the plaintext along with the tag it was pushed with.
+    ///
+    /// Returns an error if the stream has already seen a TAG_FINAL chunk,
+    /// if `ciphertext` is too short to be a valid chunk, or if
+    /// authentication fails.
+    pub fn pull(&mut self, ciphertext: &[u8]) -> Result<(Vec<u8>, u8), &'static str> {
+        if self.finalized {
+            return Err("secretstream: stream already finalized");
+        }
+        if ciphertext.len() < crypto_secretstream_OVERHEAD {
+            return Err("secretstream: ciphertext too short to be a valid chunk");
+        }
+
+        let (auth_tag, ct) = ciphertext.split_at(crypto_secretbox_OVERHEAD);
+        let nonce = chunk_nonce(&self.nonce_prefix, self.counter);
+        let framed = try!(chunk_open_detached(self.cipher, ct, auth_tag, &nonce, &self.key));
+
+        self.counter += 1;
+        let tag = framed[0];
+        match tag {
+            crypto_secretstream_TAG_FINAL => self.finalized = true,
+            crypto_secretstream_TAG_REKEY => {
+                self.key = rekey(&self.key, &self.nonce_prefix);
+                self.counter = 0;
+            },
+            _ => {},
+        }
+
+        Ok((framed[1..].to_vec(), tag))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use secretstream::{SecretStreamEncryptor, SecretStreamDecryptor,
+      SecretStreamCipher, crypto_secretstream_KEYBYTES,
+      crypto_secretstream_TAG_MESSAGE, crypto_secretstream_TAG_FINAL,
+      crypto_secretstream_TAG_REKEY};
+
+    #[test]
+    fn test_secretstream_roundtrip() {
+        let key = [0x42u8; crypto_secretstream_KEYBYTES];
+        let chunks: Vec<&[u8]> = vec!["hello, ".as_bytes(), "streaming ".as_bytes(), "world".as_bytes()];
+
+        let (mut encryptor, header) = SecretStreamEncryptor::new(&key);
+        let mut ciphertexts = Vec::new();
+        ciphertexts.push(encryptor.push(chunks[0], crypto_secretstream_TAG_MESSAGE));
+        ciphertexts.push(encryptor.push(chunks[1], crypto_secretstream_TAG_REKEY));
+        ciphertexts.push(encryptor.push(chunks[2], crypto_secretstream_TAG_FINAL));
+
+        let mut decryptor = SecretStreamDecryptor::new(&key, &header);
+        for (i, ciphertext) in ciphertexts.iter().enumerate() {
+            match decryptor.pull(&ciphertext[]) {
+                Ok((plaintext, _tag)) => assert!(&plaintext[] == chunks[i]),
+                Err(_) => panic!(),
+            }
+        }
+
+        // A finalized stream refuses to decrypt further chunks.
+        match decryptor.pull(&ciphertexts[2][]) {
+            Ok(_) => panic!(),
+            Err(_) => {},
+        }
+    }
+
+    #[test]
+    fn test_secretstream_xchacha20poly1305_roundtrip() {
+        let key = [0x42u8; crypto_secretstream_KEYBYTES];
+        let chunks: Vec<&[u8]> = vec!["hello, ".as_bytes(), "streaming ".as_bytes(), "world".as_bytes()];
+
+        let (mut encryptor, header) = SecretStreamEncryptor::new_with_cipher(&key, SecretStreamCipher::XChaCha20);
+        let mut ciphertexts = Vec::new();
+        ciphertexts.push(encryptor.push(chunks[0], crypto_secretstream_TAG_MESSAGE));
+        ciphertexts.push(encryptor.push(chunks[1], crypto_secretstream_TAG_REKEY));
+        ciphertexts.push(encryptor.push(chunks[2], crypto_secretstream_TAG_FINAL));
+
+        let mut decryptor = SecretStreamDecryptor::new_with_cipher(&key, &header, SecretStreamCipher::XChaCha20);
+        for (i, ciphertext) in ciphertexts.iter().enumerate() {
+            match decryptor.pull(&ciphertext[]) {
+                Ok((plaintext, _tag)) => assert!(&plaintext[] == chunks[i]),
+                Err(_) => panic!(),
+            }
+        }
+    }
+}